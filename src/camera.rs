@@ -0,0 +1,69 @@
+use glam::Vec3;
+
+const MIN_RADIUS: f32 = 0.5;
+const MAX_RADIUS: f32 = 20.0;
+const MAX_PITCH: f32 = 1.5;
+const ORBIT_SPEED: f32 = 0.005;
+const ZOOM_SPEED: f32 = 0.2;
+const PAN_SPEED: f32 = 0.0015;
+
+/// Spherical orbit/arcball camera: drag to rotate around `target`, drag with
+/// the secondary button to pan `target` in the view plane, scroll to zoom.
+/// `yaw`/`pitch` are in radians; `radius` is the distance from the target to
+/// the eye.
+pub struct Camera {
+    pub target: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub radius: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            target: Vec3::ZERO,
+            yaw: 0.0,
+            pitch: 0.3,
+            radius: 2.0,
+        }
+    }
+}
+
+impl Camera {
+    /// Converts the current spherical coordinates to a world-space eye
+    /// position.
+    pub fn eye(&self) -> Vec3 {
+        let horizontal = self.radius * self.pitch.cos();
+        let offset = Vec3::new(
+            horizontal * self.yaw.sin(),
+            self.radius * self.pitch.sin(),
+            horizontal * self.yaw.cos(),
+        );
+        self.target + offset
+    }
+
+    /// Rotates the camera in response to a pointer drag of `dx`, `dy`
+    /// pixels.
+    pub fn orbit(&mut self, dx: f32, dy: f32) {
+        self.yaw -= dx * ORBIT_SPEED;
+        self.pitch = (self.pitch + dy * ORBIT_SPEED).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Moves the camera toward or away from `target` in response to a
+    /// scroll-wheel notch of `delta`.
+    pub fn zoom(&mut self, delta: f32) {
+        self.radius = (self.radius - delta * ZOOM_SPEED).clamp(MIN_RADIUS, MAX_RADIUS);
+    }
+
+    /// Translates `target` within the camera's view plane in response to a
+    /// pointer drag of `dx`, `dy` pixels. Scales with `radius` so a drag
+    /// covers the same apparent distance whether the camera is close or far.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let forward = (self.target - self.eye()).normalize_or_zero();
+        let right = forward.cross(Vec3::Y).normalize_or_zero();
+        let up = right.cross(forward).normalize_or_zero();
+
+        let pan_scale = PAN_SPEED * self.radius;
+        self.target += right * (-dx * pan_scale) + up * (dy * pan_scale);
+    }
+}
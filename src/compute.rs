@@ -0,0 +1,151 @@
+use metal::*;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Triangle {
+    a: u32,
+    b: u32,
+    c: u32,
+}
+
+/// Reusable GPU compute subsystem. Built once at startup and reused across
+/// loads so a compute pre-pass doesn't need to stand up its own pipeline or
+/// command queue each time.
+pub struct ComputeContext {
+    accumulate_pipeline: ComputePipelineState,
+    normalize_pipeline: ComputePipelineState,
+}
+
+impl ComputeContext {
+    pub fn new(device: &Device) -> Self {
+        let shader_source = include_str!("shaders/compute.metal");
+        let compile_options = CompileOptions::new();
+        let library = device
+            .new_library_with_source(shader_source, &compile_options)
+            .expect("编译 compute shader 失败");
+
+        let accumulate_fn = library
+            .get_function("accumulate_face_normals", None)
+            .expect("缺少 accumulate_face_normals 函数");
+        let normalize_fn = library
+            .get_function("normalize_normals", None)
+            .expect("缺少 normalize_normals 函数");
+
+        let accumulate_pipeline = device
+            .new_compute_pipeline_state_with_function(&accumulate_fn)
+            .expect("创建 accumulate_face_normals 管线失败");
+        let normalize_pipeline = device
+            .new_compute_pipeline_state_with_function(&normalize_fn)
+            .expect("创建 normalize_normals 管线失败");
+
+        Self {
+            accumulate_pipeline,
+            normalize_pipeline,
+        }
+    }
+
+    /// Computes one area-weighted normal per entry in `positions` from
+    /// `indices` (triples forming triangles), entirely on the GPU. As with
+    /// `loader::compute_normals`, `positions` is the shared vertex list:
+    /// callers pass the original index list instead of expanding it into a
+    /// unique vertex per index.
+    pub fn generate_normals(
+        &self,
+        device: &Device,
+        command_queue: &CommandQueue,
+        positions: &[[f32; 3]],
+        indices: &[u32],
+    ) -> Vec<[f32; 3]> {
+        let vertex_count = positions.len();
+        let triangles: Vec<Triangle> = indices
+            .chunks_exact(3)
+            .map(|tri| Triangle {
+                a: tri[0],
+                b: tri[1],
+                c: tri[2],
+            })
+            .collect();
+        let triangle_count = triangles.len();
+
+        let position_buffer = device.new_buffer_with_data(
+            positions.as_ptr() as *const _,
+            (positions.len() * std::mem::size_of::<[f32; 3]>()) as u64,
+            MTLResourceOptions::CPUCacheModeDefaultCache,
+        );
+        let triangle_buffer = device.new_buffer_with_data(
+            triangles.as_ptr() as *const _,
+            (triangles.len() * std::mem::size_of::<Triangle>()) as u64,
+            MTLResourceOptions::CPUCacheModeDefaultCache,
+        );
+
+        let zeros = vec![0.0f32; vertex_count];
+        let normals_x = device.new_buffer_with_data(
+            zeros.as_ptr() as *const _,
+            (vertex_count * std::mem::size_of::<f32>()) as u64,
+            MTLResourceOptions::CPUCacheModeDefaultCache,
+        );
+        let normals_y = device.new_buffer_with_data(
+            zeros.as_ptr() as *const _,
+            (vertex_count * std::mem::size_of::<f32>()) as u64,
+            MTLResourceOptions::CPUCacheModeDefaultCache,
+        );
+        let normals_z = device.new_buffer_with_data(
+            zeros.as_ptr() as *const _,
+            (vertex_count * std::mem::size_of::<f32>()) as u64,
+            MTLResourceOptions::CPUCacheModeDefaultCache,
+        );
+        let out_normals = device.new_buffer(
+            (vertex_count * std::mem::size_of::<[f32; 3]>()) as u64,
+            MTLResourceOptions::CPUCacheModeDefaultCache,
+        );
+
+        let triangle_count_u32 = triangle_count as u32;
+        let vertex_count_u32 = vertex_count as u32;
+
+        let command_buffer = command_queue.new_command_buffer();
+
+        let accumulate_encoder = command_buffer.new_compute_command_encoder();
+        accumulate_encoder.set_compute_pipeline_state(&self.accumulate_pipeline);
+        accumulate_encoder.set_buffer(0, Some(&position_buffer), 0);
+        accumulate_encoder.set_buffer(1, Some(&triangle_buffer), 0);
+        accumulate_encoder.set_buffer(2, Some(&normals_x), 0);
+        accumulate_encoder.set_buffer(3, Some(&normals_y), 0);
+        accumulate_encoder.set_buffer(4, Some(&normals_z), 0);
+        accumulate_encoder.set_bytes(
+            5,
+            std::mem::size_of::<u32>() as u64,
+            (&triangle_count_u32 as *const u32) as *const _,
+        );
+        dispatch_linear(&accumulate_encoder, &self.accumulate_pipeline, triangle_count);
+        accumulate_encoder.end_encoding();
+
+        let normalize_encoder = command_buffer.new_compute_command_encoder();
+        normalize_encoder.set_compute_pipeline_state(&self.normalize_pipeline);
+        normalize_encoder.set_buffer(0, Some(&normals_x), 0);
+        normalize_encoder.set_buffer(1, Some(&normals_y), 0);
+        normalize_encoder.set_buffer(2, Some(&normals_z), 0);
+        normalize_encoder.set_buffer(3, Some(&out_normals), 0);
+        normalize_encoder.set_bytes(
+            4,
+            std::mem::size_of::<u32>() as u64,
+            (&vertex_count_u32 as *const u32) as *const _,
+        );
+        dispatch_linear(&normalize_encoder, &self.normalize_pipeline, vertex_count);
+        normalize_encoder.end_encoding();
+
+        command_buffer.commit();
+        command_buffer.wait_until_completed();
+
+        let out_ptr = out_normals.contents() as *const [f32; 3];
+        unsafe { std::slice::from_raw_parts(out_ptr, vertex_count).to_vec() }
+    }
+}
+
+/// Dispatches one thread per element of a 1-D workload, rounding the
+/// threadgroup count up to cover `count` at the pipeline's preferred width.
+fn dispatch_linear(encoder: &ComputeCommandEncoderRef, pipeline: &ComputePipelineState, count: usize) {
+    let thread_width = pipeline.thread_execution_width();
+    let threadgroups = MTLSize::new((count as u64 + thread_width - 1) / thread_width, 1, 1);
+    let threads_per_threadgroup = MTLSize::new(thread_width, 1, 1);
+    encoder.dispatch_thread_groups(threadgroups, threads_per_threadgroup);
+}
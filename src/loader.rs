@@ -0,0 +1,406 @@
+use crate::compute::ComputeContext;
+use crate::model::{Material, Mesh, Vertex};
+use glam::Vec3;
+use gltf::image::Format;
+use metal::*;
+use std::path::{Path, PathBuf};
+
+/// Loads `path` into one or more GPU meshes, dispatching on its extension so
+/// the same window can display either a `.obj` or a `.gltf`/`.glb` asset.
+/// `compute_ctx`, when present, offloads OBJ normal generation to the GPU
+/// instead of computing it on the CPU.
+pub fn load_model(
+    path: &Path,
+    device: &Device,
+    command_queue: &CommandQueue,
+    compute_ctx: Option<&ComputeContext>,
+) -> Vec<Mesh> {
+    match path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+    {
+        Some(ext) if ext == "gltf" || ext == "glb" => load_gltf_meshes(path, device),
+        _ => vec![load_obj_mesh(path, device, command_queue, compute_ctx)],
+    }
+}
+
+/// Finds a model to load, preferring the known sample asset, then falling
+/// back to the first `.obj`/`.gltf`/`.glb` found under `src/Models` or
+/// `Models`.
+pub fn find_model_path() -> Option<PathBuf> {
+    let preferred = Path::new("src/Models/Bunny.obj");
+    if preferred.exists() {
+        return Some(preferred.to_path_buf());
+    }
+
+    let alt_preferred = Path::new("Models/Bunny.obj");
+    if alt_preferred.exists() {
+        return Some(alt_preferred.to_path_buf());
+    }
+
+    find_first_model(Path::new("src/Models")).or_else(|| find_first_model(Path::new("Models")))
+}
+
+fn find_first_model(models_dir: &Path) -> Option<PathBuf> {
+    let entries = std::fs::read_dir(models_dir).ok()?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_model = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| {
+                let ext = ext.to_ascii_lowercase();
+                ext == "obj" || ext == "gltf" || ext == "glb"
+            })
+            .unwrap_or(false);
+        if is_model {
+            return Some(path);
+        }
+    }
+    None
+}
+
+fn load_obj_mesh(
+    path: &Path,
+    device: &Device,
+    command_queue: &CommandQueue,
+    compute_ctx: Option<&ComputeContext>,
+) -> Mesh {
+    let load_options = tobj::LoadOptions {
+        triangulate: true,
+        single_index: true,
+        ..Default::default()
+    };
+
+    let (models, _) = tobj::load_obj(path, &load_options).expect("读取 OBJ 失败");
+
+    let mut all_positions: Vec<[f32; 3]> = Vec::new();
+    for model in &models {
+        let mesh = &model.mesh;
+        for i in (0..mesh.positions.len()).step_by(3) {
+            all_positions.push([
+                mesh.positions[i],
+                mesh.positions[i + 1],
+                mesh.positions[i + 2],
+            ]);
+        }
+    }
+
+    if all_positions.is_empty() {
+        panic!("OBJ 没有顶点位置数据");
+    }
+
+    let (min, max) = bounds(&all_positions);
+    let center = [
+        (min[0] + max[0]) * 0.5,
+        (min[1] + max[1]) * 0.5,
+        (min[2] + max[2]) * 0.5,
+    ];
+    let extent = [max[0] - min[0], max[1] - min[1], max[2] - min[2]];
+    let max_extent = extent[0].max(extent[1]).max(extent[2]);
+    let scale = if max_extent > 0.0 { 2.0 / max_extent } else { 1.0 };
+
+    // Build one vertex per unique position (`single_index` already merged
+    // them on the tobj side) instead of expanding every index into its own
+    // vertex, so shared normals stay shared rather than bloating the buffer.
+    let mut vertices: Vec<Vertex> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+
+    for model in &models {
+        let mesh = &model.mesh;
+        let vertex_base = vertices.len() as u32;
+        let local_positions: Vec<[f32; 3]> = mesh
+            .positions
+            .chunks_exact(3)
+            .map(|p| [p[0], p[1], p[2]])
+            .collect();
+
+        let normals = match compute_ctx {
+            Some(ctx) => ctx
+                .generate_normals(device, command_queue, &local_positions, &mesh.indices)
+                .concat(),
+            None => compute_normals(mesh),
+        };
+
+        for (i, pos) in local_positions.iter().enumerate() {
+            let normalized = [
+                (pos[0] - center[0]) * scale,
+                (pos[1] - center[1]) * scale,
+                (pos[2] - center[2]) * scale,
+            ];
+            let normal = [normals[i * 3], normals[i * 3 + 1], normals[i * 3 + 2]];
+
+            vertices.push(Vertex {
+                position: normalized,
+                color: [0.8, 0.85, 0.9],
+                normal,
+                uv: [0.0, 0.0],
+                // OBJ meshes carry no UVs and never get a normal texture, so
+                // there's no tangent-space basis to derive; the handedness
+                // sign still needs a non-zero placeholder.
+                tangent: [1.0, 0.0, 0.0, 1.0],
+            });
+        }
+
+        for &idx in &mesh.indices {
+            indices.push(vertex_base + idx);
+        }
+    }
+
+    if indices.is_empty() {
+        panic!("OBJ 没有可绘制的索引数据");
+    }
+
+    Mesh::new(
+        device,
+        device.new_buffer_with_data(
+            vertices.as_ptr() as *const _,
+            (vertices.len() * std::mem::size_of::<Vertex>()) as u64,
+            MTLResourceOptions::CPUCacheModeDefaultCache,
+        ),
+        device.new_buffer_with_data(
+            indices.as_ptr() as *const _,
+            (indices.len() * std::mem::size_of::<u32>()) as u64,
+            MTLResourceOptions::CPUCacheModeDefaultCache,
+        ),
+        indices.len() as u64,
+        Material::default(),
+    )
+}
+
+/// Returns one normal per entry in `mesh.positions`, sharing its indexing.
+/// Uses the OBJ's own normals when present; otherwise accumulates each
+/// triangle's (unnormalized, hence area-weighted) face normal into its
+/// three vertices and normalizes the result.
+fn compute_normals(mesh: &tobj::Mesh) -> Vec<f32> {
+    if !mesh.normals.is_empty() {
+        return mesh.normals.clone();
+    }
+
+    let vertex_count = mesh.positions.len() / 3;
+    let mut normals = vec![0.0f32; vertex_count * 3];
+
+    for tri in mesh.indices.chunks_exact(3) {
+        let (ia, ib, ic) = (tri[0] as usize, tri[1] as usize, tri[2] as usize);
+        let pa = Vec3::from_slice(&mesh.positions[ia * 3..ia * 3 + 3]);
+        let pb = Vec3::from_slice(&mesh.positions[ib * 3..ib * 3 + 3]);
+        let pc = Vec3::from_slice(&mesh.positions[ic * 3..ic * 3 + 3]);
+
+        let face_normal = (pb - pa).cross(pc - pa);
+
+        for &i in &[ia, ib, ic] {
+            normals[i * 3] += face_normal.x;
+            normals[i * 3 + 1] += face_normal.y;
+            normals[i * 3 + 2] += face_normal.z;
+        }
+    }
+
+    for n in normals.chunks_exact_mut(3) {
+        let v = Vec3::new(n[0], n[1], n[2]).normalize_or_zero();
+        n[0] = v.x;
+        n[1] = v.y;
+        n[2] = v.z;
+    }
+
+    normals
+}
+
+/// Derives a per-vertex tangent (xyz) and bitangent handedness (w) from UVs,
+/// for glTF primitives that don't ship their own `TANGENT` accessor.
+/// Accumulates each triangle's tangent/bitangent (Lengyel's method) into its
+/// three vertices, then Gram-Schmidt orthogonalizes against the normal.
+fn compute_tangents(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    uvs: &[[f32; 2]],
+    indices: &[u32],
+) -> Vec<[f32; 4]> {
+    let mut tangents = vec![Vec3::ZERO; positions.len()];
+    let mut bitangents = vec![Vec3::ZERO; positions.len()];
+
+    for tri in indices.chunks_exact(3) {
+        let (ia, ib, ic) = (tri[0] as usize, tri[1] as usize, tri[2] as usize);
+        let pa = Vec3::from(positions[ia]);
+        let pb = Vec3::from(positions[ib]);
+        let pc = Vec3::from(positions[ic]);
+
+        let uva = uvs[ia];
+        let uvb = uvs[ib];
+        let uvc = uvs[ic];
+
+        let edge1 = pb - pa;
+        let edge2 = pc - pa;
+        let duv1 = [uvb[0] - uva[0], uvb[1] - uva[1]];
+        let duv2 = [uvc[0] - uva[0], uvc[1] - uva[1]];
+
+        let det = duv1[0] * duv2[1] - duv2[0] * duv1[1];
+        if det.abs() < f32::EPSILON {
+            continue;
+        }
+        let r = 1.0 / det;
+        let tangent = (edge1 * duv2[1] - edge2 * duv1[1]) * r;
+        let bitangent = (edge2 * duv1[0] - edge1 * duv2[0]) * r;
+
+        for &i in &[ia, ib, ic] {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+
+    (0..positions.len())
+        .map(|i| {
+            let n = Vec3::from(normals[i]);
+            let t = (tangents[i] - n * n.dot(tangents[i])).normalize_or_zero();
+            let handedness = if n.cross(t).dot(bitangents[i]) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            [t.x, t.y, t.z, handedness]
+        })
+        .collect()
+}
+
+fn bounds(positions: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for p in positions {
+        min[0] = min[0].min(p[0]);
+        min[1] = min[1].min(p[1]);
+        min[2] = min[2].min(p[2]);
+        max[0] = max[0].max(p[0]);
+        max[1] = max[1].max(p[1]);
+        max[2] = max[2].max(p[2]);
+    }
+    (min, max)
+}
+
+fn load_gltf_meshes(path: &Path, device: &Device) -> Vec<Mesh> {
+    let (document, buffers, images) = gltf::import(path).expect("读取 glTF 失败");
+
+    let mut meshes = Vec::new();
+    for mesh in document.meshes() {
+        for primitive in mesh.primitives() {
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+            let positions: Vec<[f32; 3]> = reader
+                .read_positions()
+                .expect("glTF primitive 缺少位置数据")
+                .collect();
+
+            let normals: Vec<[f32; 3]> = reader
+                .read_normals()
+                .map(|iter| iter.collect())
+                .unwrap_or_else(|| vec![[0.0, 1.0, 0.0]; positions.len()]);
+
+            let uvs: Vec<[f32; 2]> = reader
+                .read_tex_coords(0)
+                .map(|iter| iter.into_f32().collect())
+                .unwrap_or_else(|| vec![[0.0, 0.0]; positions.len()]);
+
+            let indices: Vec<u32> = reader
+                .read_indices()
+                .map(|iter| iter.into_u32().collect())
+                .unwrap_or_else(|| (0..positions.len() as u32).collect());
+
+            let tangents: Vec<[f32; 4]> = reader
+                .read_tangents()
+                .map(|iter| iter.collect())
+                .unwrap_or_else(|| compute_tangents(&positions, &normals, &uvs, &indices));
+
+            let vertices: Vec<Vertex> = (0..positions.len())
+                .map(|i| Vertex {
+                    position: positions[i],
+                    color: [1.0, 1.0, 1.0],
+                    normal: normals[i],
+                    uv: uvs[i],
+                    tangent: tangents[i],
+                })
+                .collect();
+
+            let material = load_material(&primitive.material(), &images, device);
+
+            meshes.push(Mesh::new(
+                device,
+                device.new_buffer_with_data(
+                    vertices.as_ptr() as *const _,
+                    (vertices.len() * std::mem::size_of::<Vertex>()) as u64,
+                    MTLResourceOptions::CPUCacheModeDefaultCache,
+                ),
+                device.new_buffer_with_data(
+                    indices.as_ptr() as *const _,
+                    (indices.len() * std::mem::size_of::<u32>()) as u64,
+                    MTLResourceOptions::CPUCacheModeDefaultCache,
+                ),
+                indices.len() as u64,
+                material,
+            ));
+        }
+    }
+
+    if meshes.is_empty() {
+        panic!("glTF 没有可绘制的网格");
+    }
+
+    meshes
+}
+
+fn load_material(material: &gltf::Material, images: &[gltf::image::Data], device: &Device) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let mut out = Material {
+        base_color_factor: pbr.base_color_factor(),
+        metallic_factor: pbr.metallic_factor(),
+        roughness_factor: pbr.roughness_factor(),
+        ..Default::default()
+    };
+
+    if let Some(info) = pbr.base_color_texture() {
+        out.base_color_texture = Some(upload_texture(&images[info.texture().source().index()], device));
+    }
+    if let Some(info) = pbr.metallic_roughness_texture() {
+        out.metallic_roughness_texture =
+            Some(upload_texture(&images[info.texture().source().index()], device));
+    }
+    if let Some(info) = material.normal_texture() {
+        out.normal_texture = Some(upload_texture(&images[info.texture().source().index()], device));
+    }
+
+    out
+}
+
+fn upload_texture(image: &gltf::image::Data, device: &Device) -> Texture {
+    let rgba = to_rgba8(image);
+
+    let desc = TextureDescriptor::new();
+    desc.set_texture_type(MTLTextureType::D2);
+    desc.set_pixel_format(MTLPixelFormat::RGBA8Unorm);
+    desc.set_width(image.width as u64);
+    desc.set_height(image.height as u64);
+    desc.set_storage_mode(MTLStorageMode::Managed);
+    desc.set_usage(MTLTextureUsage::ShaderRead);
+
+    let texture = device.new_texture(&desc);
+    let region = MTLRegion {
+        origin: MTLOrigin { x: 0, y: 0, z: 0 },
+        size: MTLSize {
+            width: image.width as u64,
+            height: image.height as u64,
+            depth: 1,
+        },
+    };
+    texture.replace_region(region, 0, rgba.as_ptr() as *const _, (image.width * 4) as u64);
+    texture
+}
+
+fn to_rgba8(image: &gltf::image::Data) -> Vec<u8> {
+    match image.format {
+        Format::R8G8B8A8 => image.pixels.clone(),
+        Format::R8G8B8 => image
+            .pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        other => panic!("不支持的 glTF 纹理格式: {:?}", other),
+    }
+}
@@ -1,27 +1,142 @@
 #![allow(unexpected_cfgs)]
 
+mod camera;
+mod compute;
+mod loader;
+mod model;
+mod scene;
+
+use block::ConcreteBlock;
+use camera::Camera;
 use cocoa::base::id;
+use compute::ComputeContext;
 use glam::{Mat4, Vec3};
 use metal::*;
+use model::Vertex;
 use objc::{msg_send, sel, sel_impl};
 use core_graphics_types::geometry::CGSize;
 use raw_window_handle::{HasWindowHandle, RawWindowHandle};
-use std::path::{Path, PathBuf};
-use winit::event::{Event, WindowEvent};
+use scene::Scene;
+use std::os::raw::c_long;
+use winit::event::{ElementState, Event, MouseButton, MouseScrollDelta, WindowEvent};
 use winit::event_loop::EventLoop;
 use winit::window::WindowBuilder;
 
-#[repr(C)]
-#[derive(Clone, Copy)]
-struct Vertex {
-    position: [f32; 3],
-    color: [f32; 3],
-}
-
 #[repr(C, align(16))]
 #[derive(Clone, Copy)]
 struct Uniforms {
     view_proj: [f32; 16],
+    light_dir: [f32; 4],
+    eye_pos: [f32; 4],
+}
+
+/// Number of frames the CPU is allowed to get ahead of the GPU.
+const FRAMES_IN_FLIGHT: usize = 3;
+
+/// MSAA sample count used by the color and depth attachments.
+const SAMPLE_COUNT: u64 = 4;
+
+/// The transient, multisampled render targets a frame draws into. Recreated
+/// whenever the drawable resizes.
+struct RenderTargets {
+    msaa_color: Texture,
+    depth: Texture,
+}
+
+#[allow(non_camel_case_types)]
+type dispatch_semaphore_t = *mut std::ffi::c_void;
+
+const DISPATCH_TIME_FOREVER: u64 = u64::MAX;
+
+extern "C" {
+    fn dispatch_semaphore_create(value: c_long) -> dispatch_semaphore_t;
+    fn dispatch_semaphore_wait(dsema: dispatch_semaphore_t, timeout: u64) -> c_long;
+    fn dispatch_semaphore_signal(dsema: dispatch_semaphore_t) -> c_long;
+}
+
+/// Thin wrapper around a GCD counting semaphore used to bound how many
+/// frames the CPU can have in flight ahead of the GPU.
+#[derive(Clone, Copy)]
+struct Semaphore(dispatch_semaphore_t);
+
+unsafe impl Send for Semaphore {}
+unsafe impl Sync for Semaphore {}
+
+impl Semaphore {
+    fn new(count: isize) -> Self {
+        Self(unsafe { dispatch_semaphore_create(count as c_long) })
+    }
+
+    fn wait(&self) {
+        unsafe {
+            dispatch_semaphore_wait(self.0, DISPATCH_TIME_FOREVER);
+        }
+    }
+
+    fn signal(&self) {
+        unsafe {
+            dispatch_semaphore_signal(self.0);
+        }
+    }
+}
+
+/// Holds one uniform buffer per in-flight frame so the CPU can write frame
+/// N+1's uniforms while the GPU is still reading frame N's. A counting
+/// semaphore keeps the CPU from getting more than `FRAMES_IN_FLIGHT` frames
+/// ahead, which would otherwise let it stomp a buffer the GPU hasn't
+/// finished with yet.
+struct FrameRing {
+    buffers: [Buffer; FRAMES_IN_FLIGHT],
+    semaphore: Semaphore,
+    frame_index: usize,
+}
+
+impl FrameRing {
+    fn new(device: &Device, layer: &MetalLayer, camera: &Camera) -> Self {
+        let uniforms = build_uniforms(layer, camera);
+        let buffers = std::array::from_fn(|_| {
+            device.new_buffer_with_data(
+                (&uniforms as *const Uniforms) as *const _,
+                std::mem::size_of::<Uniforms>() as u64,
+                MTLResourceOptions::CPUCacheModeDefaultCache,
+            )
+        });
+
+        Self {
+            buffers,
+            semaphore: Semaphore::new(FRAMES_IN_FLIGHT as isize),
+            frame_index: 0,
+        }
+    }
+
+    /// Waits for a free slot, writes this frame's uniforms into it, and
+    /// returns the buffer to bind. Must be paired with `signal_on_completion`
+    /// on the command buffer encoding this frame, before it is committed.
+    fn acquire(&mut self, layer: &MetalLayer, camera: &Camera) -> &Buffer {
+        self.semaphore.wait();
+
+        let buffer = &self.buffers[self.frame_index % FRAMES_IN_FLIGHT];
+        let uniforms = build_uniforms(layer, camera);
+        unsafe {
+            let ptr = buffer.contents() as *mut Uniforms;
+            *ptr = uniforms;
+        }
+        buffer
+    }
+
+    /// Registers a completion handler on `command_buffer` that signals the
+    /// semaphore once the GPU is done with this frame, then moves on to the
+    /// next ring slot.
+    fn signal_on_completion(&mut self, command_buffer: &CommandBufferRef) {
+        let semaphore = self.semaphore;
+        let block = ConcreteBlock::new(move |_cmd_buf: &CommandBufferRef| {
+            semaphore.signal();
+        })
+        .copy();
+        command_buffer.add_completed_handler(&block);
+
+        self.frame_index += 1;
+    }
 }
 
 fn main() {
@@ -78,6 +193,24 @@ fn main() {
         .set_format(MTLVertexFormat::Float3);
     attributes.object_at(1).unwrap().set_offset(12);
     attributes.object_at(1).unwrap().set_buffer_index(0);
+    attributes
+        .object_at(2)
+        .unwrap()
+        .set_format(MTLVertexFormat::Float3);
+    attributes.object_at(2).unwrap().set_offset(24);
+    attributes.object_at(2).unwrap().set_buffer_index(0);
+    attributes
+        .object_at(3)
+        .unwrap()
+        .set_format(MTLVertexFormat::Float2);
+    attributes.object_at(3).unwrap().set_offset(36);
+    attributes.object_at(3).unwrap().set_buffer_index(0);
+    attributes
+        .object_at(4)
+        .unwrap()
+        .set_format(MTLVertexFormat::Float4);
+    attributes.object_at(4).unwrap().set_offset(44);
+    attributes.object_at(4).unwrap().set_buffer_index(0);
 
     let layouts = vertex_desc.layouts();
     layouts
@@ -95,6 +228,7 @@ fn main() {
         .unwrap()
         .set_pixel_format(MTLPixelFormat::BGRA8Unorm);
     pipeline_desc.set_depth_attachment_pixel_format(MTLPixelFormat::Depth32Float);
+    pipeline_desc.set_sample_count(SAMPLE_COUNT);
 
     let pipeline_state = device
         .new_render_pipeline_state(&pipeline_desc)
@@ -105,46 +239,96 @@ fn main() {
     depth_desc.set_depth_write_enabled(true);
     let depth_state = device.new_depth_stencil_state(&depth_desc);
 
-    let (vertices, indices) = load_obj_mesh();
+    let compute_ctx = ComputeContext::new(&device);
 
-    let vertex_buffer = device.new_buffer_with_data(
-        vertices.as_ptr() as *const _,
-        (vertices.len() * std::mem::size_of::<Vertex>()) as u64,
-        MTLResourceOptions::CPUCacheModeDefaultCache,
-    );
+    let model_path = loader::find_model_path()
+        .unwrap_or_else(|| panic!("未找到模型：请放在 src/Models 或 Models 目录下"));
+    let mut scene = Scene::new();
+    for mesh in loader::load_model(&model_path, &device, &command_queue, Some(&compute_ctx)) {
+        scene.push(mesh, Mat4::IDENTITY);
+    }
 
-    let index_buffer = device.new_buffer_with_data(
-        indices.as_ptr() as *const _,
-        (indices.len() * std::mem::size_of::<u32>()) as u64,
-        MTLResourceOptions::CPUCacheModeDefaultCache,
-    );
+    let default_texture = create_default_texture(&device);
+    let sampler_desc = SamplerDescriptor::new();
+    sampler_desc.set_min_filter(MTLSamplerMinMagFilter::Linear);
+    sampler_desc.set_mag_filter(MTLSamplerMinMagFilter::Linear);
+    sampler_desc.set_address_mode_s(MTLSamplerAddressMode::Repeat);
+    sampler_desc.set_address_mode_t(MTLSamplerAddressMode::Repeat);
+    let sampler_state = device.new_sampler(&sampler_desc);
 
-    let mut depth_texture = resize_drawable(&device, &layer, &window);
-    let uniform_buffer = create_uniform_buffer(&device, &layer);
+    let mut camera = Camera::default();
+    let mut orbiting = false;
+    let mut panning = false;
+    let mut last_cursor: Option<(f64, f64)> = None;
+
+    let mut render_targets = resize_drawable(&device, &layer, &window);
+    let mut frame_ring = FrameRing::new(&device, &layer, &camera);
 
     let _ = event_loop.run(move |event, elwt| {
         match event {
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::CloseRequested => elwt.exit(),
                 WindowEvent::Resized(_) => {
-                    depth_texture = resize_drawable(&device, &layer, &window);
-                    update_uniform_buffer(&uniform_buffer, &layer);
+                    render_targets = resize_drawable(&device, &layer, &window);
                 }
                 WindowEvent::ScaleFactorChanged { .. } => {
-                    depth_texture = resize_drawable(&device, &layer, &window);
-                    update_uniform_buffer(&uniform_buffer, &layer);
+                    render_targets = resize_drawable(&device, &layer, &window);
+                }
+                WindowEvent::MouseInput {
+                    state,
+                    button: MouseButton::Left,
+                    ..
+                } => {
+                    orbiting = state == ElementState::Pressed;
+                    if !orbiting && !panning {
+                        last_cursor = None;
+                    }
+                }
+                WindowEvent::MouseInput {
+                    state,
+                    button: MouseButton::Right,
+                    ..
+                } => {
+                    panning = state == ElementState::Pressed;
+                    if !orbiting && !panning {
+                        last_cursor = None;
+                    }
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    if let Some((last_x, last_y)) = last_cursor {
+                        let (dx, dy) = (
+                            (position.x - last_x) as f32,
+                            (position.y - last_y) as f32,
+                        );
+                        if panning {
+                            camera.pan(dx, dy);
+                        } else if orbiting {
+                            camera.orbit(dx, dy);
+                        }
+                    }
+                    if orbiting || panning {
+                        last_cursor = Some((position.x, position.y));
+                    }
+                }
+                WindowEvent::MouseWheel { delta, .. } => {
+                    let notches = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y,
+                        MouseScrollDelta::PixelDelta(pos) => (pos.y / 20.0) as f32,
+                    };
+                    camera.zoom(notches);
                 }
                 WindowEvent::RedrawRequested => {
                     draw_frame(
                         &layer,
                         &command_queue,
                         &pipeline_state,
-                        &vertex_buffer,
-                        &index_buffer,
-                        indices.len() as u64,
-                        &depth_texture,
+                        &mut scene,
+                        &render_targets,
                         &depth_state,
-                        &uniform_buffer,
+                        &mut frame_ring,
+                        &default_texture,
+                        &sampler_state,
+                        &camera,
                     );
                 }
                 _ => {}
@@ -155,11 +339,38 @@ fn main() {
     });
 }
 
+/// A 1x1 opaque white texture bound wherever a material has no texture of
+/// its own, so every texture slot the fragment shader declares has a valid
+/// binding; the shader still checks the material's presence flags before
+/// sampling.
+fn create_default_texture(device: &Device) -> Texture {
+    let desc = TextureDescriptor::new();
+    desc.set_texture_type(MTLTextureType::D2);
+    desc.set_pixel_format(MTLPixelFormat::RGBA8Unorm);
+    desc.set_width(1);
+    desc.set_height(1);
+    desc.set_storage_mode(MTLStorageMode::Managed);
+    desc.set_usage(MTLTextureUsage::ShaderRead);
+
+    let texture = device.new_texture(&desc);
+    let region = MTLRegion {
+        origin: MTLOrigin { x: 0, y: 0, z: 0 },
+        size: MTLSize {
+            width: 1,
+            height: 1,
+            depth: 1,
+        },
+    };
+    let white_pixel: [u8; 4] = [255, 255, 255, 255];
+    texture.replace_region(region, 0, white_pixel.as_ptr() as *const _, 4);
+    texture
+}
+
 fn resize_drawable(
     device: &Device,
     layer: &MetalLayer,
     window: &winit::window::Window,
-) -> Texture {
+) -> RenderTargets {
     let size = window.inner_size();
     let scale = window.scale_factor();
     let drawable_size = CGSize::new(
@@ -168,34 +379,40 @@ fn resize_drawable(
     );
     layer.set_drawable_size(drawable_size);
 
-    create_depth_texture(device, layer)
+    create_render_targets(device, layer)
 }
 
 fn draw_frame(
     layer: &MetalLayer,
     command_queue: &CommandQueue,
     pipeline_state: &RenderPipelineState,
-    vertex_buffer: &Buffer,
-    index_buffer: &Buffer,
-    index_count: u64,
-    depth_texture: &Texture,
+    scene: &mut Scene,
+    render_targets: &RenderTargets,
     depth_state: &DepthStencilState,
-    uniform_buffer: &Buffer,
+    frame_ring: &mut FrameRing,
+    default_texture: &TextureRef,
+    sampler_state: &SamplerState,
+    camera: &Camera,
 ) {
     let drawable = match layer.next_drawable() {
         Some(drawable) => drawable,
         None => return,
     };
 
+    let uniform_buffer = frame_ring.acquire(layer, camera);
+
+    scene.sort_front_to_back(camera.eye());
+
     let pass_desc = RenderPassDescriptor::new();
     let color_attachment = pass_desc.color_attachments().object_at(0).unwrap();
-    color_attachment.set_texture(Some(drawable.texture()));
+    color_attachment.set_texture(Some(&render_targets.msaa_color));
+    color_attachment.set_resolve_texture(Some(drawable.texture()));
     color_attachment.set_load_action(MTLLoadAction::Clear);
-    color_attachment.set_store_action(MTLStoreAction::Store);
+    color_attachment.set_store_action(MTLStoreAction::MultisampleResolve);
     color_attachment.set_clear_color(MTLClearColor::new(0.1, 0.12, 0.16, 1.0));
 
     let depth_attachment = pass_desc.depth_attachment().unwrap();
-    depth_attachment.set_texture(Some(depth_texture));
+    depth_attachment.set_texture(Some(&render_targets.depth));
     depth_attachment.set_load_action(MTLLoadAction::Clear);
     depth_attachment.set_store_action(MTLStoreAction::DontCare);
     depth_attachment.set_clear_depth(1.0);
@@ -204,187 +421,104 @@ fn draw_frame(
     let encoder = command_buffer.new_render_command_encoder(&pass_desc);
     encoder.set_render_pipeline_state(pipeline_state);
     encoder.set_depth_stencil_state(depth_state);
-    encoder.set_vertex_buffer(0, Some(vertex_buffer), 0);
     encoder.set_vertex_buffer(1, Some(uniform_buffer), 0);
-    encoder.draw_indexed_primitives(
-        MTLPrimitiveType::Triangle,
-        index_count,
-        MTLIndexType::UInt32,
-        index_buffer,
-        0,
-    );
+    encoder.set_fragment_buffer(1, Some(uniform_buffer), 0);
+    encoder.set_fragment_sampler_state(0, Some(sampler_state));
+
+    for item in &scene.items {
+        let mesh = &item.mesh;
+        let draw_uniforms = item.draw_uniforms();
+
+        encoder.set_vertex_buffer(0, Some(&mesh.vertex_buffer), 0);
+        encoder.set_vertex_bytes(
+            2,
+            std::mem::size_of_val(&draw_uniforms) as u64,
+            (&draw_uniforms as *const scene::DrawUniforms) as *const _,
+        );
+        encoder.set_fragment_buffer(2, Some(&mesh.material_uniform_buffer), 0);
+        encoder.set_fragment_texture(
+            0,
+            Some(mesh.material.base_color_texture.as_deref().unwrap_or(default_texture)),
+        );
+        encoder.set_fragment_texture(
+            1,
+            Some(
+                mesh.material
+                    .metallic_roughness_texture
+                    .as_deref()
+                    .unwrap_or(default_texture),
+            ),
+        );
+        encoder.set_fragment_texture(
+            2,
+            Some(mesh.material.normal_texture.as_deref().unwrap_or(default_texture)),
+        );
+        encoder.draw_indexed_primitives(
+            MTLPrimitiveType::Triangle,
+            mesh.index_count,
+            MTLIndexType::UInt32,
+            &mesh.index_buffer,
+            0,
+        );
+    }
+
     encoder.end_encoding();
 
+    frame_ring.signal_on_completion(command_buffer);
+
     command_buffer.present_drawable(drawable);
     command_buffer.commit();
 }
 
-fn create_uniform_buffer(device: &Device, layer: &MetalLayer) -> Buffer {
-    let uniforms = build_uniforms(layer);
-    device.new_buffer_with_data(
-        (&uniforms as *const Uniforms) as *const _,
-        std::mem::size_of::<Uniforms>() as u64,
-        MTLResourceOptions::CPUCacheModeDefaultCache,
-    )
-}
-
-fn update_uniform_buffer(buffer: &Buffer, layer: &MetalLayer) {
-    let uniforms = build_uniforms(layer);
-    unsafe {
-        let ptr = buffer.contents() as *mut Uniforms;
-        *ptr = uniforms;
-    }
-}
-
-fn build_uniforms(layer: &MetalLayer) -> Uniforms {
+fn build_uniforms(layer: &MetalLayer, camera: &Camera) -> Uniforms {
     let drawable_size = layer.drawable_size();
     let aspect = (drawable_size.width as f32).max(1.0) / (drawable_size.height as f32).max(1.0);
 
-    let eye = Vec3::new(0.0, 0.0, 2.0);
-    let target = Vec3::new(0.0, 0.0, 0.0);
+    let eye = camera.eye();
+    let target = camera.target;
     let up = Vec3::new(0.0, 1.0, 0.0);
 
     let view = Mat4::look_at_rh(eye, target, up);
     let proj = Mat4::perspective_rh(45.0_f32.to_radians(), aspect, 0.1, 100.0);
     let view_proj = proj * view;
 
+    let light_dir = Vec3::new(0.5, 1.0, 0.3).normalize();
+
     Uniforms {
         view_proj: view_proj.to_cols_array(),
+        light_dir: [light_dir.x, light_dir.y, light_dir.z, 0.0],
+        eye_pos: [eye.x, eye.y, eye.z, 0.0],
     }
 }
 
-fn create_depth_texture(device: &Device, layer: &MetalLayer) -> Texture {
+/// Creates the multisampled color and depth textures a frame renders into.
+/// Both live only for the duration of the drawable's current size; the
+/// color attachment resolves into the drawable itself at the end of the
+/// render pass.
+fn create_render_targets(device: &Device, layer: &MetalLayer) -> RenderTargets {
     let drawable_size = layer.drawable_size();
-    let width = drawable_size.width as u64;
-    let height = drawable_size.height as u64;
-
-    let desc = TextureDescriptor::new();
-    desc.set_texture_type(MTLTextureType::D2);
-    desc.set_pixel_format(MTLPixelFormat::Depth32Float);
-    desc.set_width(width.max(1));
-    desc.set_height(height.max(1));
-    desc.set_storage_mode(MTLStorageMode::Private);
-    desc.set_usage(MTLTextureUsage::RenderTarget);
-
-    device.new_texture(&desc)
-}
-
-fn load_obj_mesh() -> (Vec<Vertex>, Vec<u32>) {
-    let obj_path = find_obj_path().unwrap_or_else(|| {
-        panic!("未找到 OBJ：请放在 src/Models 或 Models 目录下")
-    });
-
-    let load_options = tobj::LoadOptions {
-        triangulate: true,
-        single_index: true,
-        ..Default::default()
-    };
-
-    let (models, _) =
-        tobj::load_obj(&obj_path, &load_options).expect("读取 OBJ 失败");
-
-    let mut all_positions: Vec<[f32; 3]> = Vec::new();
-    for model in &models {
-        let mesh = &model.mesh;
-        for i in (0..mesh.positions.len()).step_by(3) {
-            all_positions.push([
-                mesh.positions[i],
-                mesh.positions[i + 1],
-                mesh.positions[i + 2],
-            ]);
-        }
-    }
-
-    if all_positions.is_empty() {
-        panic!("OBJ 没有顶点位置数据");
-    }
-
-    let (min, max) = bounds(&all_positions);
-    let center = [
-        (min[0] + max[0]) * 0.5,
-        (min[1] + max[1]) * 0.5,
-        (min[2] + max[2]) * 0.5,
-    ];
-    let extent = [max[0] - min[0], max[1] - min[1], max[2] - min[2]];
-    let max_extent = extent[0].max(extent[1]).max(extent[2]);
-    let scale = if max_extent > 0.0 { 2.0 / max_extent } else { 1.0 };
-
-    let mut vertices: Vec<Vertex> = Vec::new();
-    let mut indices: Vec<u32> = Vec::new();
-    let mut present_index: u32 = 0;
-
-    for model in &models {
-        let mesh = &model.mesh;
-        for &idx in &mesh.indices {
-            let base = idx as usize * 3;
-            let pos = [
-                mesh.positions[base],
-                mesh.positions[base + 1],
-                mesh.positions[base + 2],
-            ];
-            let normalized = [
-                (pos[0] - center[0]) * scale,
-                (pos[1] - center[1]) * scale,
-                (pos[2] - center[2]) * scale,
-            ];
-
-            vertices.push(Vertex {
-                position: normalized,
-                color: [0.8, 0.85, 0.9],
-            });
-            indices.push(present_index);
-            present_index += 1;
-        }
-    }
-
-    if indices.is_empty() {
-        panic!("OBJ 没有可绘制的索引数据");
-    }
-
-    (vertices, indices)
-}
-
-fn find_first_obj(models_dir: &Path) -> Option<PathBuf> {
-    let entries = std::fs::read_dir(models_dir).ok()?;
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if path
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .map(|ext| ext.eq_ignore_ascii_case("obj"))
-            .unwrap_or(false)
-        {
-            return Some(path);
-        }
-    }
-    None
-}
-
-fn find_obj_path() -> Option<PathBuf> {
-    let preferred = Path::new("src/Models/Bunny.obj");
-    if preferred.exists() {
-        return Some(preferred.to_path_buf());
-    }
-
-    let alt_preferred = Path::new("Models/Bunny.obj");
-    if alt_preferred.exists() {
-        return Some(alt_preferred.to_path_buf());
-    }
-
-    find_first_obj(Path::new("src/Models"))
-        .or_else(|| find_first_obj(Path::new("Models")))
-}
-
-fn bounds(positions: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
-    let mut min = [f32::INFINITY; 3];
-    let mut max = [f32::NEG_INFINITY; 3];
-    for p in positions {
-        min[0] = min[0].min(p[0]);
-        min[1] = min[1].min(p[1]);
-        min[2] = min[2].min(p[2]);
-        max[0] = max[0].max(p[0]);
-        max[1] = max[1].max(p[1]);
-        max[2] = max[2].max(p[2]);
-    }
-    (min, max)
+    let width = (drawable_size.width as u64).max(1);
+    let height = (drawable_size.height as u64).max(1);
+
+    let color_desc = TextureDescriptor::new();
+    color_desc.set_texture_type(MTLTextureType::D2Multisample);
+    color_desc.set_pixel_format(MTLPixelFormat::BGRA8Unorm);
+    color_desc.set_width(width);
+    color_desc.set_height(height);
+    color_desc.set_sample_count(SAMPLE_COUNT);
+    color_desc.set_storage_mode(MTLStorageMode::Private);
+    color_desc.set_usage(MTLTextureUsage::RenderTarget);
+    let msaa_color = device.new_texture(&color_desc);
+
+    let depth_desc = TextureDescriptor::new();
+    depth_desc.set_texture_type(MTLTextureType::D2Multisample);
+    depth_desc.set_pixel_format(MTLPixelFormat::Depth32Float);
+    depth_desc.set_width(width);
+    depth_desc.set_height(height);
+    depth_desc.set_sample_count(SAMPLE_COUNT);
+    depth_desc.set_storage_mode(MTLStorageMode::Private);
+    depth_desc.set_usage(MTLTextureUsage::RenderTarget);
+    let depth = device.new_texture(&depth_desc);
+
+    RenderTargets { msaa_color, depth }
 }
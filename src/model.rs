@@ -0,0 +1,105 @@
+use metal::*;
+
+/// A single GPU-ready vertex. Shared by every loader (OBJ, glTF) so the
+/// render pipeline only needs one vertex layout regardless of source format.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    /// xyz: tangent direction, w: bitangent handedness (+1/-1). Lets the
+    /// fragment shader build a per-vertex TBN basis for tangent-space normal
+    /// maps.
+    pub tangent: [f32; 4],
+}
+
+/// Per-draw material constants, mirrored into a uniform buffer for the
+/// fragment shader. Textures are bound as separate resources since Metal
+/// keeps resource bindings and constant data in different tables; the flags
+/// here tell the shader which textures are actually present.
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+pub struct MaterialUniforms {
+    pub base_color_factor: [f32; 4],
+    /// x: metallic_factor, y: roughness_factor, z: has_base_color_texture, w: has_metallic_roughness_texture
+    pub metallic_roughness_flags: [f32; 4],
+    /// x: has_normal_texture, yzw: unused
+    pub normal_flags: [f32; 4],
+}
+
+/// A textured PBR material following the glTF metallic-roughness model. Any
+/// texture left `None` falls back to its corresponding factor in the shader.
+pub struct Material {
+    pub base_color_factor: [f32; 4],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub base_color_texture: Option<Texture>,
+    pub metallic_roughness_texture: Option<Texture>,
+    pub normal_texture: Option<Texture>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            base_color_factor: [1.0, 1.0, 1.0, 1.0],
+            metallic_factor: 0.0,
+            roughness_factor: 1.0,
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+        }
+    }
+}
+
+impl Material {
+    pub fn to_uniforms(&self) -> MaterialUniforms {
+        MaterialUniforms {
+            base_color_factor: self.base_color_factor,
+            metallic_roughness_flags: [
+                self.metallic_factor,
+                self.roughness_factor,
+                self.base_color_texture.is_some() as u32 as f32,
+                self.metallic_roughness_texture.is_some() as u32 as f32,
+            ],
+            normal_flags: [self.normal_texture.is_some() as u32 as f32, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+/// One GPU-uploaded drawable: geometry plus the material it should be
+/// rendered with. `material_uniform_buffer` mirrors `material` and is built
+/// once at load time since materials don't change frame to frame.
+pub struct Mesh {
+    pub vertex_buffer: Buffer,
+    pub index_buffer: Buffer,
+    pub index_count: u64,
+    pub material: Material,
+    pub material_uniform_buffer: Buffer,
+}
+
+impl Mesh {
+    pub fn new(
+        device: &Device,
+        vertex_buffer: Buffer,
+        index_buffer: Buffer,
+        index_count: u64,
+        material: Material,
+    ) -> Self {
+        let uniforms = material.to_uniforms();
+        let material_uniform_buffer = device.new_buffer_with_data(
+            (&uniforms as *const MaterialUniforms) as *const _,
+            std::mem::size_of::<MaterialUniforms>() as u64,
+            MTLResourceOptions::CPUCacheModeDefaultCache,
+        );
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_count,
+            material,
+            material_uniform_buffer,
+        }
+    }
+}
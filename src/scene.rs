@@ -0,0 +1,56 @@
+use crate::model::Mesh;
+use glam::{Mat4, Vec3};
+
+/// The per-draw model matrix, uploaded via `set_vertex_bytes` since it's
+/// small enough that a dedicated buffer per item isn't worth the allocation.
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+pub struct DrawUniforms {
+    pub model: [f32; 16],
+}
+
+/// One entry in a `Scene`: a GPU mesh plus the model matrix that places it
+/// in world space.
+pub struct RenderItem {
+    pub mesh: Mesh,
+    pub model: Mat4,
+}
+
+impl RenderItem {
+    pub fn draw_uniforms(&self) -> DrawUniforms {
+        DrawUniforms {
+            model: self.model.to_cols_array(),
+        }
+    }
+}
+
+/// A batch of render items drawn within a single render pass, mirroring the
+/// render-item iteration of a typical Metal scene renderer.
+#[derive(Default)]
+pub struct Scene {
+    pub items: Vec<RenderItem>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self { items: Vec::new() }
+    }
+
+    pub fn push(&mut self, mesh: Mesh, model: Mat4) {
+        self.items.push(RenderItem { mesh, model });
+    }
+
+    /// Orders opaque items nearest-to-farthest from `eye` so the depth test
+    /// can reject occluded fragments before they reach the fragment shader.
+    pub fn sort_front_to_back(&mut self, eye: Vec3) {
+        self.items.sort_by(|a, b| {
+            let pos_a = a.model.transform_point3(Vec3::ZERO);
+            let pos_b = b.model.transform_point3(Vec3::ZERO);
+            let dist_a = pos_a.distance_squared(eye);
+            let dist_b = pos_b.distance_squared(eye);
+            dist_a
+                .partial_cmp(&dist_b)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+    }
+}